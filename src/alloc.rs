@@ -101,7 +101,7 @@ pub unsafe trait CoAllocator<M>: Allocator {
         Ok(new_slice_and_meta)
     }
 
-    unsafe fn shrink(
+    unsafe fn co_shrink(
         &self,
         ptr_and_meta: PtrAndMeta<M>,
         old_layout: Layout,
@@ -131,6 +131,38 @@ pub unsafe trait CoAllocator<M>: Allocator {
         Ok(new_slice_and_meta)
     }
 
+    /// Deprecated alias for `co_shrink`.
+    #[deprecated = "renamed to `co_shrink`"]
+    unsafe fn shrink(
+        &self,
+        ptr_and_meta: PtrAndMeta<M>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> SliceAndMetaResult<M> {
+        // SAFETY: the caller upholds the same contract as `co_shrink`'s.
+        unsafe { self.co_shrink(ptr_and_meta, old_layout, new_layout) }
+    }
+
+    /// Grows or shrinks the block of memory referenced by `ptr_and_meta` to `new_layout`,
+    /// dispatching to `co_grow` or `co_shrink` as appropriate. Provided for symmetry with
+    /// `GlobalCoAlloc::co_realloc`.
+    unsafe fn co_realloc(
+        &self,
+        ptr_and_meta: PtrAndMeta<M>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> SliceAndMetaResult<M> {
+        if new_layout.size() >= old_layout.size() {
+            // SAFETY: `new_layout.size() >= old_layout.size()`, and the caller upholds the rest
+            // of `co_grow`'s contract.
+            unsafe { self.co_grow(ptr_and_meta, old_layout, new_layout) }
+        } else {
+            // SAFETY: `new_layout.size() < old_layout.size()`, and the caller upholds the rest
+            // of `co_shrink`'s contract.
+            unsafe { self.co_shrink(ptr_and_meta, old_layout, new_layout) }
+        }
+    }
+
     fn by_ref(&self) -> &Self
     where
         Self: Sized,
@@ -138,3 +170,43 @@ pub unsafe trait CoAllocator<M>: Allocator {
         self
     }
 }
+
+// Any existing `Allocator` is trivially a cooperative allocator that has nothing to say in its
+// metadata, so the `co_*` layer is purely additive for code that doesn't care about it.
+unsafe impl<A: Allocator> CoAllocator<()> for A {
+    fn co_allocate(&self, layout: Layout) -> SliceAndMetaResult<()> {
+        self.allocate(layout)
+            .map(|slice| SliceAndMeta { slice, meta: () })
+    }
+
+    unsafe fn co_deallocate(&self, ptr_and_meta: PtrAndMeta<()>, layout: Layout) {
+        // SAFETY: the caller upholds the same contract as `Allocator::deallocate`'s.
+        unsafe { self.deallocate(ptr_and_meta.ptr, layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::Global;
+
+    #[test]
+    fn blanket_allocator_bridge_allocates_and_deallocates() {
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        let slice_and_meta = Global.co_allocate(layout).unwrap();
+        assert_eq!(slice_and_meta.meta, ());
+        assert!(slice_and_meta.slice.len() >= layout.size());
+
+        // SAFETY: `slice_and_meta` was just allocated with `layout` and hasn't been freed.
+        unsafe {
+            Global.co_deallocate(
+                PtrAndMeta {
+                    ptr: slice_and_meta.slice.as_non_null_ptr(),
+                    meta: slice_and_meta.meta,
+                },
+                layout,
+            );
+        }
+    }
+}
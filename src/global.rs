@@ -2,11 +2,19 @@ use core::alloc::{GlobalAlloc, Layout};
 use core::{cmp, ptr};
 
 /// Used for parameters and results (to/from `GlobalCoAllocator`'s functions, where applicable).
-pub struct PtrAndMeta<M> {
+pub struct GlobalPtrAndMeta<M> {
     pub ptr: *mut u8,
     pub meta: M,
 }
 
+/// Result of `co_alloc_excess`: the allocated block along with the true usable size of that
+/// block, which may exceed the size that was requested.
+pub struct ExcessAndMeta<M> {
+    pub ptr: *mut u8,
+    pub usable_size: usize,
+    pub meta: M,
+}
+
 /** Cooperative allocator. In addition to allocated memory, it returns & accepts extra metadata. That saves the allocator unnecessary processing.
  *
  * Suggest using this in `safe`, or `unsafe but correct applications only.
@@ -16,7 +24,7 @@ pub struct PtrAndMeta<M> {
  * Default function implementations are based on those from `GlobalAllocator`, with addition of preserving any metadata (of generic type `T`).
  * */
 pub unsafe trait GlobalCoAlloc<M>: GlobalAlloc {
-    unsafe fn co_alloc(&self, layout: Layout) -> PtrAndMeta<M>;
+    unsafe fn co_alloc(&self, layout: Layout) -> GlobalPtrAndMeta<M>;
 
     /// Deallocate the block of memory at the given `ptr` pointer with the given `layout`.
     ///
@@ -30,7 +38,7 @@ pub unsafe trait GlobalCoAlloc<M>: GlobalAlloc {
     ///
     /// * `layout` must be the same layout that was used
     ///   to allocate that block of memory.
-    unsafe fn co_dealloc(&self, ptr_and_meta: PtrAndMeta<M>, layout: Layout);
+    unsafe fn co_dealloc(&self, ptr_and_meta: GlobalPtrAndMeta<M>, layout: Layout);
 
     /// Behaves like `alloc`, but also ensures that the contents
     /// are set to zero before being returned.
@@ -51,7 +59,7 @@ pub unsafe trait GlobalCoAlloc<M>: GlobalAlloc {
     /// rather than directly invoking `panic!` or similar.
     ///
     /// [`handle_alloc_error`]: ../../alloc/alloc/fn.handle_alloc_error.html
-    unsafe fn co_alloc_zeroed(&self, layout: Layout) -> PtrAndMeta<M> {
+    unsafe fn co_alloc_zeroed(&self, layout: Layout) -> GlobalPtrAndMeta<M> {
         let size = layout.size();
         // SAFETY: the safety contract for `alloc` must be upheld by the caller.
         let ptr_and_meta = unsafe { self.co_alloc(layout) };
@@ -119,13 +127,46 @@ pub unsafe trait GlobalCoAlloc<M>: GlobalAlloc {
     /// [`handle_alloc_error`]: ../../alloc/alloc/fn.handle_alloc_error.html
     unsafe fn co_realloc(
         &self,
-        ptr_and_meta: PtrAndMeta<M>,
+        ptr_and_meta: GlobalPtrAndMeta<M>,
         layout: Layout,
         new_size: usize,
-    ) -> PtrAndMeta<M> {
+    ) -> GlobalPtrAndMeta<M> {
         // SAFETY: the caller must ensure that the `new_size` does not overflow.
         // `layout.align()` comes from a `Layout` and is thus guaranteed to be valid.
         let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        // SAFETY: the caller upholds `co_realloc_aligned`'s safety contract, which is the same
+        // as this function's, plus the (here trivially satisfied) requirement that `new_layout`
+        // be a valid layout.
+        unsafe { self.co_realloc_aligned(ptr_and_meta, layout, new_layout) }
+    }
+
+    /// Like `co_realloc`, but also allows the returned block's alignment to differ from
+    /// `old_layout`'s, by moving to a fresh allocation made with the full `new_layout` rather
+    /// than one derived from `old_layout`'s alignment and a bare new size.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because undefined behavior can result
+    /// if the caller does not ensure all of the following:
+    ///
+    /// * `ptr_and_meta.ptr` must be currently allocated via this allocator,
+    ///
+    /// * `old_layout` must be the same layout that was used
+    ///   to allocate that block of memory,
+    ///
+    /// * `new_layout.size()` must be greater than zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns null if `new_layout` does not meet the size
+    /// and alignment constraints of the allocator, or if reallocation
+    /// otherwise fails.
+    unsafe fn co_realloc_aligned(
+        &self,
+        ptr_and_meta: GlobalPtrAndMeta<M>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> GlobalPtrAndMeta<M> {
         // SAFETY: the caller must ensure that `new_layout` is greater than zero.
         let new_ptr_and_meta = unsafe { self.co_alloc(new_layout) };
         if !new_ptr_and_meta.ptr.is_null() {
@@ -135,11 +176,284 @@ pub unsafe trait GlobalCoAlloc<M>: GlobalAlloc {
                 ptr::copy_nonoverlapping(
                     ptr_and_meta.ptr,
                     new_ptr_and_meta.ptr,
-                    cmp::min(layout.size(), new_size),
+                    cmp::min(old_layout.size(), new_layout.size()),
+                );
+                self.co_dealloc(ptr_and_meta, old_layout);
+            }
+        }
+        new_ptr_and_meta
+    }
+
+    /// Grows the block of memory referenced by `ptr_and_meta` from `old_layout` to
+    /// `new_layout`, by allocating a fresh block and copying the old contents into it.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as `co_realloc_aligned`, plus `new_layout.size() >= old_layout.size()`.
+    unsafe fn co_grow(
+        &self,
+        ptr_and_meta: GlobalPtrAndMeta<M>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> GlobalPtrAndMeta<M> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        // SAFETY: the caller upholds `co_realloc_aligned`'s contract.
+        unsafe { self.co_realloc_aligned(ptr_and_meta, old_layout, new_layout) }
+    }
+
+    /// Like `co_grow`, but the newly grown portion of the block is zeroed.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as `co_grow`.
+    unsafe fn co_grow_zeroed(
+        &self,
+        ptr_and_meta: GlobalPtrAndMeta<M>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> GlobalPtrAndMeta<M> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        // SAFETY: the caller upholds `co_alloc_zeroed`'s contract for `new_layout`.
+        let new_ptr_and_meta = unsafe { self.co_alloc_zeroed(new_layout) };
+        if !new_ptr_and_meta.ptr.is_null() {
+            // SAFETY: the previously allocated block cannot overlap the newly allocated block.
+            // The safety contract for `dealloc` must be upheld by the caller.
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    ptr_and_meta.ptr,
+                    new_ptr_and_meta.ptr,
+                    old_layout.size(),
                 );
-                self.co_dealloc(ptr_and_meta, layout);
+                self.co_dealloc(ptr_and_meta, old_layout);
             }
         }
         new_ptr_and_meta
     }
+
+    /// Shrinks the block of memory referenced by `ptr_and_meta` from `old_layout` to
+    /// `new_layout`, by allocating a fresh, smaller block and copying the retained contents
+    /// into it.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as `co_realloc_aligned`, plus `new_layout.size() <= old_layout.size()`.
+    unsafe fn co_shrink(
+        &self,
+        ptr_and_meta: GlobalPtrAndMeta<M>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> GlobalPtrAndMeta<M> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        // SAFETY: the caller upholds `co_realloc_aligned`'s contract.
+        unsafe { self.co_realloc_aligned(ptr_and_meta, old_layout, new_layout) }
+    }
+
+    /// Behaves like `co_alloc`, but also reports the usable size of the returned block, which
+    /// may exceed `layout.size()`. Allocators backed by size-class schemes can override this to
+    /// report the true, rounded-up block size, letting a caller that tracks its own capacity
+    /// (the same role `CoAllocator::co_allocate`'s returned slice length plays for a
+    /// `GlobalAlloc`-free caller) use the extra space without going back to the allocator.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as `co_alloc`.
+    unsafe fn co_alloc_excess(&self, layout: Layout) -> ExcessAndMeta<M> {
+        // SAFETY: the caller upholds `co_alloc`'s contract.
+        let ptr_and_meta = unsafe { self.co_alloc(layout) };
+        ExcessAndMeta {
+            ptr: ptr_and_meta.ptr,
+            usable_size: layout.size(),
+            meta: ptr_and_meta.meta,
+        }
+    }
+}
+
+// Any existing `GlobalAlloc` is trivially a cooperative allocator that has nothing to say in
+// its metadata, so the `co_*` layer is purely additive for code that doesn't care about it.
+unsafe impl<A: GlobalAlloc> GlobalCoAlloc<()> for A {
+    unsafe fn co_alloc(&self, layout: Layout) -> GlobalPtrAndMeta<()> {
+        // SAFETY: the caller upholds the same contract as `GlobalAlloc::alloc`'s.
+        let ptr = unsafe { self.alloc(layout) };
+        GlobalPtrAndMeta { ptr, meta: () }
+    }
+
+    unsafe fn co_dealloc(&self, ptr_and_meta: GlobalPtrAndMeta<()>, layout: Layout) {
+        // SAFETY: the caller upholds the same contract as `GlobalAlloc::dealloc`'s.
+        unsafe { self.dealloc(ptr_and_meta.ptr, layout) }
+    }
+}
+
+/// Adapter that lets a `GlobalCoAlloc<()>` cooperative allocator (with unit metadata) be
+/// installed as the process's `#[global_allocator]`, discarding the zero-sized metadata on
+/// every call.
+pub struct AsGlobal<A>(pub A);
+
+unsafe impl<A: GlobalCoAlloc<()>> GlobalAlloc for AsGlobal<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: the caller upholds the same contract as `co_alloc`'s.
+        unsafe { self.0.co_alloc(layout) }.ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: the caller upholds the same contract as `co_dealloc`'s.
+        unsafe { self.0.co_dealloc(GlobalPtrAndMeta { ptr, meta: () }, layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    #[test]
+    fn co_realloc_aligned_moves_to_the_new_alignment_and_preserves_data() {
+        let old_layout = Layout::from_size_align(8, 8).unwrap();
+        let new_layout = Layout::from_size_align(8, 64).unwrap();
+
+        // SAFETY: `old_layout` has a non-zero size.
+        let ptr = unsafe { System.alloc(old_layout) };
+        assert!(!ptr.is_null());
+        // SAFETY: `ptr` was just allocated with `old_layout`.
+        unsafe { ptr.write_bytes(0xAB, old_layout.size()) };
+
+        // SAFETY: `ptr` is currently allocated via `System` with `old_layout`, using the
+        // blanket `GlobalCoAlloc<()>` impl.
+        let grown = unsafe {
+            System.co_realloc_aligned(GlobalPtrAndMeta { ptr, meta: () }, old_layout, new_layout)
+        };
+        assert!(!grown.ptr.is_null());
+        assert_eq!(grown.ptr as usize % new_layout.align(), 0);
+
+        // SAFETY: `grown.ptr` is valid for `old_layout.size()` bytes, just copied over.
+        let bytes = unsafe { core::slice::from_raw_parts(grown.ptr, old_layout.size()) };
+        assert!(bytes.iter().all(|&b| b == 0xAB));
+
+        // SAFETY: `grown.ptr` was allocated above with `new_layout`.
+        unsafe { System.dealloc(grown.ptr, new_layout) };
+    }
+
+    #[test]
+    fn co_grow_preserves_the_old_contents() {
+        let old_layout = Layout::from_size_align(4, 4).unwrap();
+        let new_layout = Layout::from_size_align(16, 4).unwrap();
+
+        // SAFETY: `old_layout` has a non-zero size.
+        let ptr = unsafe { System.alloc(old_layout) };
+        assert!(!ptr.is_null());
+        // SAFETY: `ptr` was just allocated with `old_layout`.
+        unsafe { ptr.write_bytes(0xCD, old_layout.size()) };
+
+        // SAFETY: `ptr` is currently allocated via `System` with `old_layout`.
+        let grown =
+            unsafe { System.co_grow(GlobalPtrAndMeta { ptr, meta: () }, old_layout, new_layout) };
+        assert!(!grown.ptr.is_null());
+        // SAFETY: `grown.ptr` is valid for `old_layout.size()` bytes, just copied over.
+        let bytes = unsafe { core::slice::from_raw_parts(grown.ptr, old_layout.size()) };
+        assert!(bytes.iter().all(|&b| b == 0xCD));
+
+        // SAFETY: `grown.ptr` was allocated above with `new_layout`.
+        unsafe { System.dealloc(grown.ptr, new_layout) };
+    }
+
+    #[test]
+    fn co_grow_zeroed_keeps_old_contents_and_zeroes_the_grown_tail() {
+        let old_layout = Layout::from_size_align(4, 4).unwrap();
+        let new_layout = Layout::from_size_align(16, 4).unwrap();
+
+        // SAFETY: `old_layout` has a non-zero size.
+        let ptr = unsafe { System.alloc(old_layout) };
+        assert!(!ptr.is_null());
+        // SAFETY: `ptr` was just allocated with `old_layout`.
+        unsafe { ptr.write_bytes(0xEF, old_layout.size()) };
+
+        // SAFETY: `ptr` is currently allocated via `System` with `old_layout`.
+        let grown = unsafe {
+            System.co_grow_zeroed(GlobalPtrAndMeta { ptr, meta: () }, old_layout, new_layout)
+        };
+        assert!(!grown.ptr.is_null());
+        // SAFETY: `grown.ptr` is valid for `new_layout.size()` bytes.
+        let bytes = unsafe { core::slice::from_raw_parts(grown.ptr, new_layout.size()) };
+        assert!(bytes[..old_layout.size()].iter().all(|&b| b == 0xEF));
+        assert!(bytes[old_layout.size()..].iter().all(|&b| b == 0));
+
+        // SAFETY: `grown.ptr` was allocated above with `new_layout`.
+        unsafe { System.dealloc(grown.ptr, new_layout) };
+    }
+
+    #[test]
+    fn co_shrink_truncates_and_preserves_the_retained_prefix() {
+        let old_layout = Layout::from_size_align(16, 4).unwrap();
+        let new_layout = Layout::from_size_align(4, 4).unwrap();
+
+        // SAFETY: `old_layout` has a non-zero size.
+        let ptr = unsafe { System.alloc(old_layout) };
+        assert!(!ptr.is_null());
+        // SAFETY: `ptr` was just allocated with `old_layout`.
+        unsafe { ptr.write_bytes(0x12, old_layout.size()) };
+
+        // SAFETY: `ptr` is currently allocated via `System` with `old_layout`.
+        let shrunk =
+            unsafe { System.co_shrink(GlobalPtrAndMeta { ptr, meta: () }, old_layout, new_layout) };
+        assert!(!shrunk.ptr.is_null());
+        // SAFETY: `shrunk.ptr` is valid for `new_layout.size()` bytes.
+        let bytes = unsafe { core::slice::from_raw_parts(shrunk.ptr, new_layout.size()) };
+        assert!(bytes.iter().all(|&b| b == 0x12));
+
+        // SAFETY: `shrunk.ptr` was allocated above with `new_layout`.
+        unsafe { System.dealloc(shrunk.ptr, new_layout) };
+    }
+
+    #[test]
+    fn blanket_global_alloc_bridge_allocates_and_deallocates() {
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        // SAFETY: `layout` has a non-zero size.
+        let ptr_and_meta = unsafe { System.co_alloc(layout) };
+        assert!(!ptr_and_meta.ptr.is_null());
+        assert_eq!(ptr_and_meta.meta, ());
+
+        // SAFETY: `ptr_and_meta` was just allocated with `layout` and hasn't been freed.
+        unsafe { System.co_dealloc(ptr_and_meta, layout) };
+    }
+
+    #[test]
+    fn as_global_adapter_round_trips_through_the_wrapped_co_allocator() {
+        let wrapped = AsGlobal(System);
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        // SAFETY: `layout` has a non-zero size.
+        let ptr = unsafe { wrapped.alloc(layout) };
+        assert!(!ptr.is_null());
+        // SAFETY: `ptr` was just allocated with `layout`.
+        unsafe { ptr.write_bytes(0x34, layout.size()) };
+
+        // SAFETY: `ptr` is currently allocated via `wrapped` with `layout`.
+        unsafe { wrapped.dealloc(ptr, layout) };
+    }
+
+    #[test]
+    fn co_alloc_excess_default_impl_reports_the_requested_size() {
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        // SAFETY: `layout` has a non-zero size.
+        let excess = unsafe { System.co_alloc_excess(layout) };
+        assert!(!excess.ptr.is_null());
+        assert_eq!(excess.usable_size, layout.size());
+        assert_eq!(excess.meta, ());
+
+        // SAFETY: `excess.ptr` was just allocated with `layout` and hasn't been freed.
+        unsafe { System.dealloc(excess.ptr, layout) };
+    }
 }
@@ -2,10 +2,14 @@
 
 mod alloc;
 mod global;
+mod raw_vec;
+mod segregated;
 
 // Re-export
 pub use alloc::*;
 pub use global::*;
+pub use raw_vec::*;
+pub use segregated::*;
 
 // -------
 // --------
@@ -0,0 +1,251 @@
+use core::alloc::Layout;
+use core::cmp;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
+
+use crate::alloc::{CoAllocator, PtrAndMeta};
+
+/// Error returned by `CoRawVec`'s fallible reservation methods.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryReserveError {
+    /// The requested capacity exceeds `isize::MAX` bytes, or computing the array layout
+    /// overflowed `usize`.
+    CapacityOverflow,
+    /// The allocator returned an error while allocating or growing the backing memory.
+    AllocError {
+        /// The layout that was requested from the allocator.
+        layout: Layout,
+    },
+}
+
+/// Low-level, growable buffer that threads a cooperative allocator's metadata `M` from one
+/// `co_allocate`/`co_grow` call to the next, so the allocator never has to re-derive it (e.g.
+/// size-class info) on a later call.
+///
+/// `CoRawVec` does not track a length; it only owns the backing allocation, its capacity, and
+/// the metadata handed back by the allocator. Building a `Vec`-like type with a length on top is
+/// left to the caller.
+pub struct CoRawVec<T, M, A: CoAllocator<M>> {
+    ptr: NonNull<T>,
+    cap: usize,
+    meta: Option<M>,
+    alloc: A,
+    _marker: PhantomData<T>,
+}
+
+impl<T, M, A: CoAllocator<M>> CoRawVec<T, M, A> {
+    /// `cap` for a zero-sized `T`: there is never anything to allocate, so capacity is
+    /// unbounded.
+    const ZST_CAP: usize = usize::MAX;
+
+    /// Smallest capacity `grow_amortized` will allocate, matching the policy used by the
+    /// standard library's `RawVec` so that small buffers don't immediately re-grow.
+    const MIN_NON_ZERO_CAP: usize = if mem::size_of::<T>() == 1 {
+        8
+    } else if mem::size_of::<T>() <= 1024 {
+        4
+    } else {
+        1
+    };
+
+    /// Creates an empty `CoRawVec` with no backing allocation.
+    pub fn new(alloc: A) -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            cap: if mem::size_of::<T>() == 0 { Self::ZST_CAP } else { 0 },
+            meta: None,
+            alloc,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements this buffer can currently hold.
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Returns a pointer to the backing allocation.
+    pub fn ptr(&self) -> NonNull<T> {
+        self.ptr
+    }
+
+    /// Returns a reference to the allocator backing this buffer.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Ensures the buffer's capacity is at least `additional`, growing (by doubling, as per
+    /// `grow_amortized`) if needed.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>
+    where
+        M: Clone,
+    {
+        if additional > self.cap {
+            self.grow_amortized(additional)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn grow_amortized(&mut self, required_cap: usize) -> Result<(), TryReserveError>
+    where
+        M: Clone,
+    {
+        if mem::size_of::<T>() == 0 {
+            // ZSTs are never actually allocated; capacity is always `usize::MAX`.
+            return Ok(());
+        }
+
+        let new_cap = cmp::max(self.cap.saturating_mul(2), required_cap);
+        let new_cap = cmp::max(new_cap, Self::MIN_NON_ZERO_CAP);
+
+        let new_layout =
+            Layout::array::<T>(new_cap).map_err(|_| TryReserveError::CapacityOverflow)?;
+
+        let slice_and_meta = if self.cap == 0 {
+            self.alloc
+                .co_allocate(new_layout)
+                .map_err(|_| TryReserveError::AllocError { layout: new_layout })?
+        } else {
+            // Clone (rather than take) the stored metadata: `co_grow` takes it by value, but on
+            // `Err` the old block is left allocated and unchanged, so `self.meta` must still
+            // describe it afterwards. Only overwrite `self.meta` once the new allocation is
+            // confirmed below.
+            let meta = self
+                .meta
+                .as_ref()
+                .expect("allocated buffer must carry metadata")
+                .clone();
+            // SAFETY: `self.ptr`/`meta` were obtained from this allocator via a prior
+            // `co_allocate`/`co_grow` call using `old_layout`, which hasn't been deallocated.
+            let old_layout = Layout::array::<T>(self.cap).unwrap();
+            unsafe {
+                self.alloc
+                    .co_grow(
+                        PtrAndMeta {
+                            ptr: self.ptr.cast(),
+                            meta,
+                        },
+                        old_layout,
+                        new_layout,
+                    )
+                    .map_err(|_| TryReserveError::AllocError { layout: new_layout })?
+            }
+        };
+
+        self.ptr = slice_and_meta.slice.as_non_null_ptr().cast();
+        self.cap = slice_and_meta.slice.len() / mem::size_of::<T>();
+        self.meta = Some(slice_and_meta.meta);
+
+        Ok(())
+    }
+}
+
+impl<T, M, A: CoAllocator<M>> Drop for CoRawVec<T, M, A> {
+    fn drop(&mut self) {
+        if self.cap != 0 && mem::size_of::<T>() != 0 {
+            let layout = Layout::array::<T>(self.cap).unwrap();
+            let meta = self.meta.take().expect("allocated buffer must carry metadata");
+            // SAFETY: `self.ptr`/`meta` were returned together by this allocator for `layout`,
+            // and this is the only place that deallocates them.
+            unsafe {
+                self.alloc.co_deallocate(
+                    PtrAndMeta {
+                        ptr: self.ptr.cast(),
+                        meta,
+                    },
+                    layout,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::alloc::{AllocError, Allocator};
+    use core::cell::Cell;
+    use std::alloc::Global;
+
+    #[test]
+    fn first_allocation_reports_capacity() {
+        let mut vec: CoRawVec<u32, (), Global> = CoRawVec::new(Global);
+        assert_eq!(vec.capacity(), 0);
+
+        vec.try_reserve(1).unwrap();
+        assert!(vec.capacity() >= 1);
+    }
+
+    #[test]
+    fn growth_doubles_amortized_capacity() {
+        let mut vec: CoRawVec<u32, (), Global> = CoRawVec::new(Global);
+        vec.try_reserve(1).unwrap();
+        let first_cap = vec.capacity();
+
+        vec.try_reserve(first_cap + 1).unwrap();
+        assert!(vec.capacity() >= first_cap * 2);
+    }
+
+    #[test]
+    fn zst_capacity_is_unbounded_and_never_allocates() {
+        let mut vec: CoRawVec<(), (), Global> = CoRawVec::new(Global);
+        assert_eq!(vec.capacity(), usize::MAX);
+
+        vec.try_reserve(1 << 30).unwrap();
+        assert_eq!(vec.capacity(), usize::MAX);
+    }
+
+    #[test]
+    fn capacity_overflow_is_reported() {
+        let mut vec: CoRawVec<u32, (), Global> = CoRawVec::new(Global);
+        let err = vec.try_reserve(usize::MAX).unwrap_err();
+        assert_eq!(err, TryReserveError::CapacityOverflow);
+    }
+
+    /// Fails every other call to `allocate`, to exercise `CoRawVec`'s fallible-growth error
+    /// paths.
+    struct FlakyAlloc {
+        calls: Cell<u32>,
+    }
+
+    impl FlakyAlloc {
+        fn new() -> Self {
+            Self {
+                calls: Cell::new(0),
+            }
+        }
+    }
+
+    unsafe impl Allocator for FlakyAlloc {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let call = self.calls.get();
+            self.calls.set(call + 1);
+            if call % 2 == 1 {
+                return Err(AllocError);
+            }
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            // SAFETY: forwarded verbatim; the caller upholds `Allocator::deallocate`'s contract.
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn failed_grow_surfaces_error_without_corrupting_the_vec() {
+        let mut vec: CoRawVec<u32, (), FlakyAlloc> = CoRawVec::new(FlakyAlloc::new());
+        vec.try_reserve(1).unwrap();
+        let cap_before = vec.capacity();
+
+        let err = vec.try_reserve(cap_before * 4).unwrap_err();
+        assert!(matches!(err, TryReserveError::AllocError { .. }));
+        assert_eq!(vec.capacity(), cap_before);
+
+        // Must still be safely droppable: the old block's metadata wasn't lost by the failed
+        // grow above.
+        drop(vec);
+    }
+}
@@ -0,0 +1,352 @@
+use core::alloc::{AllocError, Allocator, Layout};
+use core::cell::Cell;
+use core::cmp;
+use core::ptr::{self, NonNull};
+use std::alloc::Global;
+
+use crate::alloc::{CoAllocator, PtrAndMeta, SliceAndMeta, SliceAndMetaResult};
+
+/// Number of size-segregated buckets. Bucket `i` holds blocks of `1 << (MIN_BUCKET_SHIFT + i)`
+/// bytes, so with `MIN_BUCKET_SHIFT = 4` the buckets cover 16 B through 2048 B.
+const NUM_BUCKETS: usize = 8;
+const MIN_BUCKET_SHIFT: u32 = 4;
+
+/// Metadata returned by `SegregatedCoAlloc`: which bucket (if any) a block was carved from.
+/// Handing this back to `co_deallocate`/`co_grow`/`co_shrink` lets the allocator push the block
+/// straight onto the right free list, instead of re-deriving the bucket from `layout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketTag {
+    /// The block came from the bucket at this index.
+    Class(u8),
+    /// The block didn't fit any bucket and was served straight from the inner allocator.
+    Large,
+}
+
+/// Intrusive free-list node. While a block is free, its own memory stores the link to the next
+/// free block in the same bucket.
+struct FreeNode {
+    next: Option<NonNull<FreeNode>>,
+}
+
+/// Size-segregated free-list allocator. Requests are rounded up to the smallest bucket that
+/// fits, served from that bucket's intrusive free list (falling back to the inner allocator
+/// `A` when the list is empty), and oversized requests fall through to `A` directly.
+///
+/// The metadata type is `BucketTag`, the bucket a block was carved from, so `co_deallocate`
+/// never has to recompute it from `layout` the way a plain `Allocator::deallocate` must.
+pub struct SegregatedCoAlloc<A: Allocator = Global> {
+    buckets: [Cell<Option<NonNull<FreeNode>>>; NUM_BUCKETS],
+    inner: A,
+}
+
+// SAFETY: the free-list links are raw `NonNull` pointers (so not `Send` automatically), but they
+// only point into blocks this allocator obtained from `inner` and owns; moving the allocator to
+// another thread moves that ownership with it. No `Sync` impl is provided, since the free lists
+// live in `Cell`s that `co_allocate`/`co_deallocate` mutate through `&self`.
+unsafe impl<A: Allocator + Send> Send for SegregatedCoAlloc<A> {}
+
+impl SegregatedCoAlloc<Global> {
+    /// Creates a `SegregatedCoAlloc` backed by the global allocator.
+    pub fn new() -> Self {
+        Self::with_allocator(Global)
+    }
+}
+
+impl Default for SegregatedCoAlloc<Global> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Allocator> SegregatedCoAlloc<A> {
+    /// Creates a `SegregatedCoAlloc` backed by `inner` for page-sized refills and large
+    /// requests.
+    pub fn with_allocator(inner: A) -> Self {
+        Self {
+            buckets: core::array::from_fn(|_| Cell::new(None)),
+            inner,
+        }
+    }
+
+    /// Byte size of the blocks held in bucket `i`.
+    fn block_size(i: usize) -> usize {
+        1usize << (MIN_BUCKET_SHIFT as usize + i)
+    }
+
+    /// Picks the smallest bucket whose blocks satisfy `layout`, if any. Buckets are
+    /// self-aligned (block `i`'s blocks are aligned to `block_size(i)`), so a bucket fits a
+    /// layout whenever its block size is at least `max(layout.size(), layout.align())`.
+    fn bucket_for(layout: Layout) -> Option<(u8, usize)> {
+        let needed = cmp::max(layout.size(), layout.align());
+        (0..NUM_BUCKETS).find_map(|i| {
+            let block_size = Self::block_size(i);
+            (needed <= block_size).then_some((i as u8, block_size))
+        })
+    }
+
+    fn layout_for_bucket(block_size: usize) -> Layout {
+        Layout::from_size_align(block_size, block_size).unwrap()
+    }
+
+    /// Pops a block off bucket `i`'s free list. If the list is empty, allocates a fresh page
+    /// from `inner`, returns its first block and pushes the page's other blocks onto the list.
+    fn allocate_from_bucket(&self, i: u8, block_size: usize) -> Result<NonNull<u8>, AllocError> {
+        if let Some(node) = self.buckets[i as usize].get() {
+            // SAFETY: `node` was pushed by a prior `deallocate_into_bucket` call for this same
+            // bucket, so it points at a live `FreeNode` header written into a still-allocated
+            // block.
+            let next = unsafe { node.as_ref().next };
+            self.buckets[i as usize].set(next);
+            Ok(node.cast())
+        } else {
+            const PAGE_SIZE: usize = 4096;
+            let page_size = cmp::max(PAGE_SIZE, block_size);
+            let page = self
+                .inner
+                .allocate(Layout::from_size_align(page_size, block_size).unwrap())?;
+            let base = page.as_non_null_ptr();
+            for k in 1..page_size / block_size {
+                // SAFETY: block `k` lies inside the page just allocated, is aligned to
+                // `block_size`, and is not handed out anywhere else.
+                unsafe { self.deallocate_into_bucket(base.add(k * block_size), i) };
+            }
+            Ok(base)
+        }
+    }
+
+    /// Pushes `ptr` (a block from bucket `i`) back onto that bucket's free list.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must currently be an allocated block obtained from bucket `i`, and the caller must
+    /// not use `ptr` afterwards until it is handed back out by `allocate_from_bucket`. As with
+    /// the rest of `SegregatedCoAlloc`'s free-list bookkeeping, this assumes single-threaded
+    /// access.
+    unsafe fn deallocate_into_bucket(&self, ptr: NonNull<u8>, i: u8) {
+        let node = ptr.cast::<FreeNode>();
+        // SAFETY: the block is at least `block_size(i) >= 16` bytes, large enough for a
+        // `FreeNode` header, and the caller guarantees it is currently allocated (so writing to
+        // it is valid) and not aliased elsewhere.
+        unsafe {
+            node.as_ptr().write(FreeNode {
+                next: self.buckets[i as usize].get(),
+            });
+        }
+        self.buckets[i as usize].set(Some(node));
+    }
+}
+
+unsafe impl<A: Allocator> CoAllocator<BucketTag> for SegregatedCoAlloc<A> {
+    fn co_allocate(&self, layout: Layout) -> SliceAndMetaResult<BucketTag> {
+        match Self::bucket_for(layout) {
+            Some((i, block_size)) => {
+                let ptr = self.allocate_from_bucket(i, block_size)?;
+                Ok(SliceAndMeta {
+                    slice: NonNull::slice_from_raw_parts(ptr, block_size),
+                    meta: BucketTag::Class(i),
+                })
+            }
+            None => {
+                let slice = self.inner.allocate(layout)?;
+                Ok(SliceAndMeta {
+                    slice,
+                    meta: BucketTag::Large,
+                })
+            }
+        }
+    }
+
+    unsafe fn co_deallocate(&self, ptr_and_meta: PtrAndMeta<BucketTag>, layout: Layout) {
+        match ptr_and_meta.meta {
+            // SAFETY: the caller guarantees `ptr_and_meta.ptr` is currently allocated from
+            // bucket `i`, as recorded by the `BucketTag` this allocator itself returned.
+            BucketTag::Class(i) => unsafe { self.deallocate_into_bucket(ptr_and_meta.ptr, i) },
+            // SAFETY: the caller upholds `Allocator::deallocate`'s contract.
+            BucketTag::Large => unsafe { self.inner.deallocate(ptr_and_meta.ptr, layout) },
+        }
+    }
+
+    unsafe fn co_grow(
+        &self,
+        ptr_and_meta: PtrAndMeta<BucketTag>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> SliceAndMetaResult<BucketTag> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        if let BucketTag::Class(i) = ptr_and_meta.meta {
+            let block_size = Self::block_size(i as usize);
+            if cmp::max(new_layout.size(), new_layout.align()) <= block_size {
+                return Ok(SliceAndMeta {
+                    slice: NonNull::slice_from_raw_parts(ptr_and_meta.ptr, block_size),
+                    meta: BucketTag::Class(i),
+                });
+            }
+        }
+
+        let new_slice_and_meta = self.co_allocate(new_layout)?;
+        // SAFETY: `new_layout.size() >= old_layout.size()`, and the old block wasn't yet
+        // deallocated, so it cannot overlap the new one.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                ptr_and_meta.ptr.as_ptr(),
+                new_slice_and_meta.slice.as_mut_ptr(),
+                old_layout.size(),
+            );
+            self.co_deallocate(ptr_and_meta, old_layout);
+        }
+        Ok(new_slice_and_meta)
+    }
+
+    unsafe fn co_shrink(
+        &self,
+        ptr_and_meta: PtrAndMeta<BucketTag>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> SliceAndMetaResult<BucketTag> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        if let BucketTag::Class(i) = ptr_and_meta.meta {
+            let block_size = Self::block_size(i as usize);
+            if cmp::max(new_layout.size(), new_layout.align()) <= block_size {
+                return Ok(SliceAndMeta {
+                    slice: NonNull::slice_from_raw_parts(ptr_and_meta.ptr, block_size),
+                    meta: BucketTag::Class(i),
+                });
+            }
+        }
+
+        let new_slice_and_meta = self.co_allocate(new_layout)?;
+        // SAFETY: `new_layout.size() <= old_layout.size()`, and the old block wasn't yet
+        // deallocated, so it cannot overlap the new one.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                ptr_and_meta.ptr.as_ptr(),
+                new_slice_and_meta.slice.as_mut_ptr(),
+                new_layout.size(),
+            );
+            self.co_deallocate(ptr_and_meta, old_layout);
+        }
+        Ok(new_slice_and_meta)
+    }
+}
+
+// `CoAllocator` requires `Allocator`. A plain `deallocate` call carries no metadata, so this
+// impl has to re-derive the bucket from `layout` on every call — exactly the "unnecessary
+// processing" that handing back the `BucketTag` via `co_deallocate` avoids.
+unsafe impl<A: Allocator> Allocator for SegregatedCoAlloc<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        <Self as CoAllocator<BucketTag>>::co_allocate(self, layout).map(|s| s.slice)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let meta = match Self::bucket_for(layout) {
+            Some((i, _)) => BucketTag::Class(i),
+            None => BucketTag::Large,
+        };
+        // SAFETY: the caller upholds `Allocator::deallocate`'s contract, and `meta` was just
+        // re-derived from `layout` using the same rule `co_allocate` used to pick it.
+        unsafe { self.co_deallocate(PtrAndMeta { ptr, meta }, layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_and_frees_within_a_bucket() {
+        let alloc = SegregatedCoAlloc::new();
+        let layout = Layout::from_size_align(10, 1).unwrap();
+
+        let first = co_allocate(&alloc, layout).unwrap();
+        assert_eq!(first.meta, BucketTag::Class(0));
+        assert!(first.slice.len() >= 10);
+
+        // SAFETY: `first` was just allocated with `layout` and hasn't been freed yet.
+        unsafe {
+            co_deallocate(
+                &alloc,
+                PtrAndMeta {
+                    ptr: first.slice.as_non_null_ptr(),
+                    meta: first.meta,
+                },
+                layout,
+            );
+        }
+    }
+
+    #[test]
+    fn reuses_freed_blocks_from_the_same_bucket() {
+        let alloc = SegregatedCoAlloc::new();
+        let layout = Layout::from_size_align(10, 1).unwrap();
+
+        let first = co_allocate(&alloc, layout).unwrap();
+        let first_ptr = first.slice.as_non_null_ptr();
+        // SAFETY: `first` was just allocated with `layout` and hasn't been freed yet.
+        unsafe {
+            co_deallocate(
+                &alloc,
+                PtrAndMeta {
+                    ptr: first_ptr,
+                    meta: first.meta,
+                },
+                layout,
+            );
+        }
+
+        let second = co_allocate(&alloc, layout).unwrap();
+        assert_eq!(second.slice.as_non_null_ptr(), first_ptr);
+    }
+
+    #[test]
+    fn picks_distinct_buckets_by_size() {
+        let small = SegregatedCoAlloc::<Global>::bucket_for(Layout::from_size_align(10, 1).unwrap());
+        let large =
+            SegregatedCoAlloc::<Global>::bucket_for(Layout::from_size_align(1000, 1).unwrap());
+        assert_ne!(small, large);
+    }
+
+    #[test]
+    fn oversized_requests_fall_through_to_large() {
+        let alloc = SegregatedCoAlloc::new();
+        let layout = Layout::from_size_align(1 << 20, 1).unwrap();
+
+        let block = co_allocate(&alloc, layout).unwrap();
+        assert_eq!(block.meta, BucketTag::Large);
+
+        // SAFETY: `block` was just allocated with `layout` and hasn't been freed yet.
+        unsafe {
+            co_deallocate(
+                &alloc,
+                PtrAndMeta {
+                    ptr: block.slice.as_non_null_ptr(),
+                    meta: block.meta,
+                },
+                layout,
+            );
+        }
+    }
+
+    // Disambiguates against the blanket `CoAllocator<()>` impl that also applies to
+    // `SegregatedCoAlloc<A>` through its plain `Allocator` impl.
+    fn co_allocate(alloc: &SegregatedCoAlloc, layout: Layout) -> SliceAndMetaResult<BucketTag> {
+        CoAllocator::<BucketTag>::co_allocate(alloc, layout)
+    }
+
+    unsafe fn co_deallocate(
+        alloc: &SegregatedCoAlloc,
+        ptr_and_meta: PtrAndMeta<BucketTag>,
+        layout: Layout,
+    ) {
+        // SAFETY: forwarded verbatim; the caller upholds `CoAllocator::co_deallocate`'s
+        // contract.
+        unsafe { CoAllocator::<BucketTag>::co_deallocate(alloc, ptr_and_meta, layout) }
+    }
+}